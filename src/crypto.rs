@@ -0,0 +1,367 @@
+//! Key-protection algorithms used by JKS and JCEKS keystores.
+//!
+//! Private keys use Sun's proprietary JKS scheme: it XORs the key against a
+//! SHA-1 keystream seeded from the password and a salt, and authenticates
+//! the result with a second SHA-1 digest rather than an HMAC. Secret keys in
+//! a JCEKS store instead use the more conventional (if still proprietary)
+//! `PBEWithMD5AndTripleDES`, an iterated-MD5 key derivation feeding a
+//! DES-EDE3-CBC cipher.
+
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+
+use crate::Error;
+
+/// DER encoding of the OID body `1.3.6.1.4.1.42.2.17.1.1`, Sun's
+/// proprietary key-protection algorithm identifier.
+const SUN_JKS_ALGORITHM_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x2A, 0x02, 0x11, 0x01, 0x01];
+
+/// Converts a password to the big-endian UTF-16 byte encoding the JDK uses
+/// for both the key-protection keystream and the keystore integrity check.
+pub(crate) fn password_to_utf16be(password: &str) -> Vec<u8> {
+    password
+        .encode_utf16()
+        .flat_map(|unit| unit.to_be_bytes())
+        .collect()
+}
+
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    Sha1::digest(data).into()
+}
+
+/// Decrypts a JKS-protected `EncryptedPrivateKeyInfo` DER blob and returns
+/// the recovered PKCS#8 `PrivateKeyInfo` bytes.
+pub(crate) fn decrypt_jks_key(
+    encrypted_private_key_info: &[u8],
+    password: &str,
+) -> Result<Vec<u8>, Error> {
+    let encrypted_data = extract_encrypted_octets(encrypted_private_key_info)?;
+    if encrypted_data.len() < 40 {
+        return Err(Error::truncated(40));
+    }
+
+    let salt = &encrypted_data[..20];
+    let check_offset = encrypted_data.len() - 20;
+    let ciphertext = &encrypted_data[20..check_offset];
+    let check = &encrypted_data[check_offset..];
+
+    let passwd_bytes = password_to_utf16be(password);
+    let keystream = derive_keystream(&passwd_bytes, salt, ciphertext.len());
+
+    let plain: Vec<u8> = ciphertext
+        .iter()
+        .zip(keystream.iter())
+        .map(|(c, k)| c ^ k)
+        .collect();
+
+    let mut check_input = passwd_bytes;
+    check_input.extend_from_slice(&plain);
+    if sha1(&check_input) != check {
+        return Err(Error::KeyDecryptFailed);
+    }
+
+    Ok(plain)
+}
+
+/// Encrypts a PKCS#8 `PrivateKeyInfo` with a freshly generated salt and
+/// returns the resulting `EncryptedPrivateKeyInfo` DER, the inverse of
+/// [`decrypt_jks_key`].
+pub(crate) fn encrypt_jks_key(private_key_info: &[u8], password: &str) -> Vec<u8> {
+    let mut salt = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let passwd_bytes = password_to_utf16be(password);
+    let keystream = derive_keystream(&passwd_bytes, &salt, private_key_info.len());
+    let ciphertext: Vec<u8> = private_key_info
+        .iter()
+        .zip(keystream.iter())
+        .map(|(p, k)| p ^ k)
+        .collect();
+
+    let mut check_input = passwd_bytes;
+    check_input.extend_from_slice(private_key_info);
+    let check = sha1(&check_input);
+
+    let mut encrypted_data = Vec::with_capacity(40 + ciphertext.len());
+    encrypted_data.extend_from_slice(&salt);
+    encrypted_data.extend_from_slice(&ciphertext);
+    encrypted_data.extend_from_slice(&check);
+
+    encode_encrypted_private_key_info(&encrypted_data)
+}
+
+/// Builds `len` bytes of keystream in 20-byte SHA-1 blocks: block 0 is
+/// `SHA1(password || salt)`, every later block is `SHA1(password ||
+/// previous_digest)`.
+fn derive_keystream(passwd_bytes: &[u8], salt: &[u8], len: usize) -> Vec<u8> {
+    let mut keystream = Vec::with_capacity(len + 20);
+    let mut input = passwd_bytes.to_vec();
+    input.extend_from_slice(salt);
+    let mut digest = sha1(&input);
+    while keystream.len() < len {
+        keystream.extend_from_slice(&digest);
+        input = passwd_bytes.to_vec();
+        input.extend_from_slice(&digest);
+        digest = sha1(&input);
+    }
+    keystream.truncate(len);
+    keystream
+}
+
+/// Pulls the `encryptedData` octet string out of a DER-encoded
+/// `EncryptedPrivateKeyInfo { encryptionAlgorithm, encryptedData }`,
+/// ignoring the algorithm identifier (expected to be Sun's OID
+/// `1.3.6.1.4.1.42.2.17.1.1`).
+fn extract_encrypted_octets(der: &[u8]) -> Result<&[u8], Error> {
+    let (outer_tag, outer_content, _) = read_tlv(der, 0)?;
+    if outer_tag != 0x30 {
+        return Err(Error::Asn1(
+            "expected a DER SEQUENCE for EncryptedPrivateKeyInfo".to_owned(),
+        ));
+    }
+    let (alg_tag, _alg_content, alg_end) = read_tlv(outer_content, 0)?;
+    if alg_tag != 0x30 {
+        return Err(Error::Asn1(
+            "expected a DER SEQUENCE for AlgorithmIdentifier".to_owned(),
+        ));
+    }
+    let (data_tag, data_content, _) = read_tlv(outer_content, alg_end)?;
+    if data_tag != 0x04 {
+        return Err(Error::Asn1(
+            "expected a DER OCTET STRING for encryptedData".to_owned(),
+        ));
+    }
+    Ok(data_content)
+}
+
+/// Builds the `EncryptedPrivateKeyInfo { encryptionAlgorithm, encryptedData
+/// }` DER wrapper around an already-encrypted `salt || ciphertext || check`
+/// blob, the inverse of [`extract_encrypted_octets`].
+fn encode_encrypted_private_key_info(encrypted_data: &[u8]) -> Vec<u8> {
+    let algorithm_identifier = encode_tlv(0x30, &encode_tlv(0x06, SUN_JKS_ALGORITHM_OID));
+    let encrypted_data = encode_tlv(0x04, encrypted_data);
+
+    let mut content = algorithm_identifier;
+    content.extend_from_slice(&encrypted_data);
+    encode_tlv(0x30, &content)
+}
+
+/// Encodes a single DER tag-length-value, using long-form lengths only when
+/// `content` is 128 bytes or longer.
+fn encode_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let first_significant = len_bytes.iter().position(|b| *b != 0).unwrap_or(len_bytes.len() - 1);
+        let significant = &len_bytes[first_significant..];
+        out.push(0x80 | significant.len() as u8);
+        out.extend_from_slice(significant);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Reads a single DER tag-length-value starting at `pos`, returning the tag,
+/// the content slice, and the offset just past the value.
+pub(crate) fn read_tlv(data: &[u8], pos: usize) -> Result<(u8, &[u8], usize), Error> {
+    let too_short = || Error::Asn1("unexpected end of DER data".to_owned());
+
+    if pos >= data.len() {
+        return Err(too_short());
+    }
+    let tag = data[pos];
+    let mut idx = pos + 1;
+    let first_len_byte = *data.get(idx).ok_or_else(too_short)?;
+    idx += 1;
+
+    let len = if first_len_byte & 0x80 == 0 {
+        first_len_byte as usize
+    } else {
+        let num_bytes = (first_len_byte & 0x7f) as usize;
+        let len_bytes = data.get(idx..idx + num_bytes).ok_or_else(too_short)?;
+        idx += num_bytes;
+        len_bytes
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize)
+    };
+
+    let content = data
+        .get(idx..idx + len)
+        .ok_or_else(|| Error::Asn1("DER length exceeds available data".to_owned()))?;
+    Ok((tag, content, idx + len))
+}
+
+/// Decrypts a JCEKS `SecretKeyEntry`'s sealed content, protected with Sun's
+/// `PBEWithMD5AndTripleDES` scheme: see [`derive_jceks_key_iv`] for the key
+/// and IV derivation, which then decrypt `encrypted_content` under CBC with
+/// PKCS#5 padding.
+///
+/// This reconstructs the derivation from public descriptions of
+/// `com.sun.crypto.provider.PBES1Core`. It is checked in this crate via the
+/// round trip against [`encrypt_jceks_secret_key`] below (see
+/// `jceks_pbe_encrypt_decrypt_round_trip`) and a second, independently
+/// written derivation in `derive_jceks_key_iv_matches_independent_reimplementation`
+/// — neither of which can catch a shared misunderstanding of the algorithm,
+/// since this crate has no way to produce or check against a real
+/// JDK-generated JCEKS file in this environment. Treat this as a best-effort
+/// implementation pending that verification, not a confirmed one.
+pub(crate) fn decrypt_jceks_secret_key(
+    encrypted_content: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    password: &str,
+) -> Result<Vec<u8>, Error> {
+    use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+    use des::TdesEde3;
+
+    let (key, iv) = derive_jceks_key_iv(salt, iterations, password)?;
+    type TdesCbcDec = cbc::Decryptor<TdesEde3>;
+    let decryptor = TdesCbcDec::new_from_slices(&key, &iv)
+        .map_err(|e| Error::Asn1(format!("invalid JCEKS secret-key material: {e}")))?;
+    decryptor
+        .decrypt_padded_vec_mut::<cbc::cipher::block_padding::Pkcs7>(encrypted_content)
+        .map_err(|_| Error::KeyDecryptFailed)
+}
+
+/// Encrypts `plaintext` with the same `PBEWithMD5AndTripleDES` scheme
+/// [`decrypt_jceks_secret_key`] reverses; used to build a round-trip test
+/// fixture since no real JDK-generated JCEKS file is available here.
+#[cfg(test)]
+pub(crate) fn encrypt_jceks_secret_key(
+    plaintext: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    password: &str,
+) -> Result<Vec<u8>, Error> {
+    use cbc::cipher::{BlockEncryptMut, KeyIvInit};
+    use des::TdesEde3;
+
+    let (key, iv) = derive_jceks_key_iv(salt, iterations, password)?;
+    type TdesCbcEnc = cbc::Encryptor<TdesEde3>;
+    let encryptor = TdesCbcEnc::new_from_slices(&key, &iv)
+        .map_err(|e| Error::Asn1(format!("invalid JCEKS secret-key material: {e}")))?;
+    Ok(encryptor.encrypt_padded_vec_mut::<cbc::cipher::block_padding::Pkcs7>(plaintext))
+}
+
+/// Derives the 24-byte DES-EDE3 key and 8-byte IV `PBEWithMD5AndTripleDES`
+/// uses, in 16-byte MD5 blocks: the first block digests the password and
+/// the first 8 bytes of `salt`, iterated `iterations` times; each further
+/// block re-mixes the password, salt and *previous* block before iterating
+/// again, the same way [`derive_keystream`] extends a SHA-1 keystream by
+/// re-mixing the password into every block rather than re-hashing the
+/// running digest alone.
+fn derive_jceks_key_iv(
+    salt: &[u8],
+    iterations: u32,
+    password: &str,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    if salt.len() < 8 {
+        return Err(Error::truncated(8));
+    }
+    let salt = &salt[..8];
+    let passwd_bytes = password.as_bytes();
+
+    let mut key_material = Vec::with_capacity(32);
+    let mut previous_block: Option<[u8; 16]> = None;
+    while key_material.len() < 32 {
+        let mut input = passwd_bytes.to_vec();
+        input.extend_from_slice(salt);
+        if let Some(block) = previous_block {
+            input.extend_from_slice(&block);
+        }
+
+        let mut digest = md5_digest(&input);
+        for _ in 1..iterations.max(1) {
+            digest = md5_digest(&digest);
+        }
+        key_material.extend_from_slice(&digest);
+        previous_block = Some(digest);
+    }
+    key_material.truncate(32);
+    let (key, iv) = key_material.split_at(24);
+    Ok((key.to_vec(), iv.to_vec()))
+}
+
+fn md5_digest(data: &[u8]) -> [u8; 16] {
+    use md5::Digest;
+    md5::Md5::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_encrypt_decrypt_round_trip() {
+        let private_key_info = b"not a real PrivateKeyInfo, just some bytes".to_vec();
+        let encrypted = encrypt_jks_key(&private_key_info, "swordfish");
+        let decrypted = decrypt_jks_key(&encrypted, "swordfish").unwrap();
+        assert_eq!(decrypted, private_key_info);
+    }
+
+    #[test]
+    fn wrong_password_fails_the_check_digest() {
+        let private_key_info = b"not a real PrivateKeyInfo, just some bytes".to_vec();
+        let encrypted = encrypt_jks_key(&private_key_info, "swordfish");
+        assert!(matches!(
+            decrypt_jks_key(&encrypted, "wrong password"),
+            Err(Error::KeyDecryptFailed)
+        ));
+    }
+
+    #[test]
+    fn jceks_pbe_encrypt_decrypt_round_trip() {
+        let plaintext = b"not a real serialized SecretKeySpec, just some bytes".to_vec();
+        let salt = [7u8; 8];
+        let iterations = 1000;
+        let encrypted =
+            encrypt_jceks_secret_key(&plaintext, &salt, iterations, "swordfish").unwrap();
+        let decrypted =
+            decrypt_jceks_secret_key(&encrypted, &salt, iterations, "swordfish").unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    /// Recomputes the same key/IV material with a from-scratch MD5
+    /// derivation, written independently of [`derive_jceks_key_iv`]'s block
+    /// loop, so a coding mistake in that loop would show up here even
+    /// though this cannot catch a shared misunderstanding of Sun's
+    /// algorithm (see the caveat on [`decrypt_jceks_secret_key`]).
+    #[test]
+    fn derive_jceks_key_iv_matches_independent_reimplementation() {
+        use md5::{Digest, Md5};
+
+        fn reference_derive(salt: &[u8; 8], iterations: u32, password: &str) -> [u8; 32] {
+            let mut block0_input = password.as_bytes().to_vec();
+            block0_input.extend_from_slice(salt);
+            let mut block0: [u8; 16] = Md5::digest(&block0_input).into();
+            for _ in 1..iterations {
+                block0 = Md5::digest(block0).into();
+            }
+
+            let mut block1_input = password.as_bytes().to_vec();
+            block1_input.extend_from_slice(salt);
+            block1_input.extend_from_slice(&block0);
+            let mut block1: [u8; 16] = Md5::digest(&block1_input).into();
+            for _ in 1..iterations {
+                block1 = Md5::digest(block1).into();
+            }
+
+            let mut material = [0u8; 32];
+            material[..16].copy_from_slice(&block0);
+            material[16..].copy_from_slice(&block1);
+            material
+        }
+
+        let salt = [3u8; 8];
+        let iterations = 250;
+        let password = "hunter2";
+
+        let (key, iv) = derive_jceks_key_iv(&salt, iterations, password).unwrap();
+        let expected = reference_derive(&salt, iterations, password);
+        assert_eq!(key, &expected[..24]);
+        assert_eq!(iv, &expected[24..]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Structured error type for the crate, in the spirit of the error module in
+//! Mozilla's rsclientcerts: every failure mode is a distinct variant a
+//! caller can match on, and truncation errors capture the call site that
+//! noticed the short read.
+
+use std::fmt;
+use std::panic::Location;
+
+use crate::MAGIC;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The file does not start with the JKS magic bytes.
+    BadMagic { got: [u8; 4] },
+    /// The file declares a format version this crate does not support.
+    UnsupportedVersion(u32),
+    /// An entry's type tag was not `1` (key pair), `2` (trusted cert) or `3`
+    /// (JCEKS secret key).
+    InvalidEntryType(u32),
+    /// The buffer ended before a length-prefixed field could be read.
+    Truncated {
+        needed: usize,
+        at: &'static Location<'static>,
+    },
+    /// A certificate entry declared a type other than `"X.509"`.
+    BadCertType(String),
+    /// DER decoding failed, either in our own minimal parser or in
+    /// `x509-certificate`; also used when a JCEKS secret-key entry's
+    /// serialized `SealedObject` or its protection algorithm is not
+    /// supported.
+    Asn1(String),
+    /// An alias or other length-prefixed string was not valid UTF-8.
+    Utf8(String),
+    /// An I/O error that was not simply running out of buffer.
+    Io(String),
+    /// The keystore's trailing integrity digest did not match.
+    IntegrityCheckFailed,
+    /// A decrypted private key's check digest did not match, or a decrypted
+    /// JCEKS secret key's padding was invalid.
+    KeyDecryptFailed,
+}
+
+impl Error {
+    #[track_caller]
+    pub(crate) fn truncated(needed: usize) -> Self {
+        Error::Truncated {
+            needed,
+            at: Location::caller(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadMagic { got } => write!(
+                f,
+                "invalid file format, expected header '{:#x?}', but got '{:#x?}'",
+                MAGIC, got
+            ),
+            Error::UnsupportedVersion(v) => {
+                write!(f, "unsupported version {v}, supported versions are 1 and 2")
+            }
+            Error::InvalidEntryType(t) => write!(f, "invalid entry type: {t}"),
+            Error::Truncated { needed, at } => write!(
+                f,
+                "buffer is too short, at least {needed} bytes are required ({at})"
+            ),
+            Error::BadCertType(t) => write!(f, "not supported certificate type: {t}"),
+            Error::Asn1(msg) => write!(f, "failed to parse ASN.1 data: {msg}"),
+            Error::Utf8(msg) => write!(f, "invalid UTF-8: {msg}"),
+            Error::Io(msg) => write!(f, "error reading bytes: {msg}"),
+            Error::IntegrityCheckFailed => write!(
+                f,
+                "keystore integrity check failed, wrong password or corrupted file"
+            ),
+            Error::KeyDecryptFailed => write!(
+                f,
+                "key integrity check failed, wrong password or corrupted key"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        Error::Utf8(e.to_string())
+    }
+}
+
+impl From<x509_certificate::X509CertificateError> for Error {
+    fn from(e: x509_certificate::X509CertificateError) -> Self {
+        Error::Asn1(e.to_string())
+    }
+}
@@ -1,28 +1,40 @@
+mod crypto;
+mod error;
+mod jceks;
+pub mod raw;
 pub mod read;
+mod write;
 
-use core::panic;
-use std::collections::HashMap;
-use std::error::Error;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Write};
 
-use read::read_cert;
+use read::JksReader;
+use write::{write_cert_entry, write_key_pair_entry};
 use x509_certificate::certificate::X509Certificate;
 
-const MAGIC: [u8; 4] = [0xFE, 0xED, 0xFE, 0xED];
+pub use error::Error;
 
-/// Java KeyStore version
-/// Support only Version 2
-#[derive(PartialEq)]
+pub(crate) const MAGIC: [u8; 4] = [0xFE, 0xED, 0xFE, 0xED];
+
+/// Java KeyStore version.
+///
+/// V1 is the older, pre-JCE on-wire format. Entry framing is identical to
+/// V2's; the only difference this crate implements is that V1 omits
+/// [`INTEGRITY_SALT`] from the trailing integrity digest.
+#[derive(PartialEq, Clone, Copy)]
 enum Version {
-    Unsupported,
+    V1,
     V2,
 }
 
-impl From<[u8; 4]> for Version {
-    fn from(value: [u8; 4]) -> Self {
-        match u32::from_be_bytes(value) {
-            2 => Version::V2,
-            _ => Version::Unsupported,
+impl TryFrom<[u8; 4]> for Version {
+    type Error = Error;
+
+    fn try_from(value: [u8; 4]) -> Result<Self, Error> {
+        let version = u32::from_be_bytes(value);
+        match version {
+            1 => Ok(Version::V1),
+            2 => Ok(Version::V2),
+            _ => Err(Error::UnsupportedVersion(version)),
         }
     }
 }
@@ -31,14 +43,20 @@ impl From<[u8; 4]> for Version {
 enum EntryType {
     KeyPair,
     Certs,
+    /// A JCEKS `SecretKeyEntry`, absent from plain JKS stores.
+    SecretKey,
 }
 
-impl From<[u8; 4]> for EntryType {
-    fn from(value: [u8; 4]) -> Self {
-        match u32::from_be_bytes(value) {
-            1 => EntryType::KeyPair,
-            2 => EntryType::Certs,
-            _ => panic!("invalid entry type"),
+impl TryFrom<[u8; 4]> for EntryType {
+    type Error = Error;
+
+    fn try_from(value: [u8; 4]) -> Result<Self, Error> {
+        let entry_type = u32::from_be_bytes(value);
+        match entry_type {
+            1 => Ok(EntryType::KeyPair),
+            2 => Ok(EntryType::Certs),
+            3 => Ok(EntryType::SecretKey),
+            _ => Err(Error::InvalidEntryType(entry_type)),
         }
     }
 }
@@ -47,6 +65,7 @@ impl From<[u8; 4]> for EntryType {
 pub struct Store {
     pub certs: Vec<CertInfo>,
     pub key_pairs: Vec<KeyPair>,
+    pub secret_keys: Vec<SecretKeyEntry>,
 }
 
 #[derive(Debug)]
@@ -70,6 +89,43 @@ pub struct KeyPair {
     pub cert_chain: Vec<KeyPairCert>,
 }
 
+impl KeyPair {
+    /// Decrypts `encrypted_key` with Sun's proprietary JKS key-protection
+    /// algorithm and returns the recovered PKCS#8 `PrivateKeyInfo` DER.
+    pub fn decrypt(&self, password: &str) -> Result<Vec<u8>, Error> {
+        crypto::decrypt_jks_key(&self.encrypted_key, password)
+    }
+}
+
+/// A JCEKS `SecretKeyEntry`: a symmetric key wrapped in a Java-serialized
+/// `SealedObject`, protected with `PBEWithMD5AndTripleDES`.
+#[derive(Debug)]
+pub struct SecretKeyEntry {
+    pub alias: String,
+    pub timestamp: u64,
+    pub sealed_object: Vec<u8>,
+}
+
+impl SecretKeyEntry {
+    /// Decrypts the sealed key material and returns the recovered bytes: the
+    /// Java-serialized form of whatever `Key` the entry wraps (typically a
+    /// `SecretKeySpec`), left undecoded since this crate does not implement
+    /// a general Java deserializer.
+    pub fn decrypt(&self, password: &str) -> Result<Vec<u8>, Error> {
+        let mut sealed = std::io::Cursor::new(&self.sealed_object);
+        let fields = jceks::read_sealed_object(&mut sealed)?;
+        if fields.params_alg != "PBEWithMD5AndTripleDES" || fields.seal_alg != "PBEWithMD5AndTripleDES"
+        {
+            return Err(Error::Asn1(format!(
+                "unsupported secret-key protection algorithm: {}",
+                fields.seal_alg
+            )));
+        }
+        let (salt, iterations) = jceks::parse_pbe_params(&fields.encoded_params)?;
+        crypto::decrypt_jceks_secret_key(&fields.encrypted_content, &salt, iterations, password)
+    }
+}
+
 #[derive(Debug)]
 pub struct KeyPairCert {
     pub raw: Vec<u8>,
@@ -80,7 +136,6 @@ pub struct KeyPairCert {
 pub struct Options {
     pub password: String,
     pub skip_verify: bool,
-    pub key_passwords: HashMap<String, String>,
 }
 
 impl Default for Options {
@@ -88,50 +143,226 @@ impl Default for Options {
         Options {
             password: "changeit".to_owned(),
             skip_verify: false,
-            key_passwords: HashMap::new(),
         }
     }
 }
 
+/// Trailer appended to the keystore's own password when computing the
+/// integrity digest; a constant Sun has kept stable since JDK 1.2.
+const INTEGRITY_SALT: &[u8] = b"Mighty Aphrodite";
+
 impl Store {
-    pub fn parse(data: impl AsRef<[u8]>, opts: Option<Options>) -> Result<Self, Box<dyn Error>> {
-        let mut buffer = BufReader::new(data.as_ref());
-
-        let magic = read::read_u32(&mut buffer)?;
-        if !magic.eq(&MAGIC) {
-            return Err(format!(
-                "invalid file format, expected header '{:#x?}', but got '{:#x?}'",
-                MAGIC, magic
-            ))?;
-        }
-        let version = read::read_u32(&mut buffer)?;
-        if Version::from(version) == Version::Unsupported {
-            return Err("unsupported version, supported only version 2".to_owned())?;
+    /// Reads the keystore header and returns a lazy iterator over its
+    /// entries, decoding one at a time rather than collecting everything
+    /// into `Vec<CertInfo>`/`Vec<KeyPair>` up front.
+    pub fn entries<R: JksReader>(
+        mut reader: R,
+    ) -> Result<impl Iterator<Item = Result<Entry, Error>>, Error> {
+        let magic = reader.read_u32()?;
+        if magic != MAGIC {
+            return Err(Error::BadMagic { got: magic });
         }
+        Version::try_from(reader.read_u32()?)?;
+
+        let remaining = u32::from_be_bytes(reader.read_u32()?);
+        Ok(EntryIter { reader, remaining })
+    }
+
+    pub fn parse(data: impl AsRef<[u8]>, opts: Option<Options>) -> Result<Self, Error> {
+        let bytes = data.as_ref();
+        let opts = opts.unwrap_or_default();
+        let version_bytes: [u8; 4] = bytes
+            .get(4..8)
+            .ok_or_else(|| Error::truncated(8))?
+            .try_into()
+            .expect("slice of length 4");
+        let version = Version::try_from(version_bytes)?;
+
         let mut key_pairs: Vec<KeyPair> = vec![];
         let mut certs: Vec<CertInfo> = vec![];
-        let mut opts = opts.unwrap_or_default();
-
-        let entries = u32::from_be_bytes(read::read_u32(&mut buffer)?);
-        for _ in 0..entries {
-            let entry_type = EntryType::from(read::read_u32(&mut buffer)?);
-            match entry_type {
-                EntryType::KeyPair => key_pairs.push(process_key_pair(&mut buffer, &mut opts)?),
-                EntryType::Certs => certs.push(process_cert(&mut buffer)?),
+        let mut secret_keys: Vec<SecretKeyEntry> = vec![];
+        for entry in Store::entries(BufReader::new(bytes))? {
+            match entry? {
+                Entry::KeyPair(key_pair) => key_pairs.push(key_pair),
+                Entry::Cert(cert) => certs.push(*cert),
+                Entry::SecretKey(secret_key) => secret_keys.push(secret_key),
             }
         }
 
-        Ok(Store { certs, key_pairs })
+        if !opts.skip_verify {
+            verify_integrity(bytes, version, &opts.password)?;
+        }
+
+        Ok(Store {
+            certs,
+            key_pairs,
+            secret_keys,
+        })
+    }
+
+    /// Serializes this store back to JKS bytes, in the same on-wire framing
+    /// `parse` consumes, and appends the trailing integrity MAC computed
+    /// with `opts.password`.
+    pub fn write<W: Write>(&self, w: &mut W, opts: &Options) -> Result<(), Error> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&MAGIC);
+        body.extend_from_slice(&2u32.to_be_bytes());
+
+        let entry_count =
+            (self.key_pairs.len() + self.certs.len() + self.secret_keys.len()) as u32;
+        body.extend_from_slice(&entry_count.to_be_bytes());
+
+        for key_pair in &self.key_pairs {
+            write_key_pair_entry(&mut body, key_pair);
+        }
+        for cert in &self.certs {
+            write_cert_entry(&mut body, cert);
+        }
+        for secret_key in &self.secret_keys {
+            write::write_secret_key_entry(&mut body, secret_key);
+        }
+
+        let mut mac_input = crypto::password_to_utf16be(&opts.password);
+        mac_input.extend_from_slice(INTEGRITY_SALT);
+        mac_input.extend_from_slice(&body);
+
+        w.write_all(&body).map_err(|e| Error::Io(e.to_string()))?;
+        w.write_all(&crypto::sha1(&mac_input))
+            .map_err(|e| Error::Io(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Incrementally builds a [`Store`] from scratch, encrypting each private
+/// key with a freshly generated salt as it is added.
+#[derive(Debug, Default)]
+pub struct StoreBuilder {
+    certs: Vec<CertInfo>,
+    key_pairs: Vec<KeyPair>,
+    secret_keys: Vec<SecretKeyEntry>,
+}
+
+impl StoreBuilder {
+    pub fn new() -> Self {
+        StoreBuilder::default()
+    }
+
+    pub fn add_cert(
+        mut self,
+        alias: impl Into<String>,
+        timestamp: u64,
+        der: Vec<u8>,
+    ) -> Result<Self, Error> {
+        let cert = X509Certificate::from_der(der.clone())?;
+        self.certs.push(CertInfo {
+            alias: alias.into(),
+            timestamp,
+            certificate: Cert { raw: der, cert },
+        });
+        Ok(self)
+    }
+
+    pub fn add_key_pair(
+        mut self,
+        alias: impl Into<String>,
+        timestamp: u64,
+        private_key_info_der: &[u8],
+        password: &str,
+        cert_chain_der: Vec<Vec<u8>>,
+    ) -> Result<Self, Error> {
+        let encrypted_key = crypto::encrypt_jks_key(private_key_info_der, password);
+        let mut cert_chain = Vec::with_capacity(cert_chain_der.len());
+        for der in cert_chain_der {
+            let cert = X509Certificate::from_der(der.clone())?;
+            cert_chain.push(KeyPairCert { raw: der, cert });
+        }
+        self.key_pairs.push(KeyPair {
+            alias: alias.into(),
+            timestamp,
+            encrypted_key,
+            cert_chain,
+        });
+        Ok(self)
+    }
+
+    pub fn build(self) -> Store {
+        Store {
+            certs: self.certs,
+            key_pairs: self.key_pairs,
+            secret_keys: self.secret_keys,
+        }
+    }
+}
+
+/// Checks the keyed SHA-1 digest the JDK appends to every keystore: the
+/// trailing 20 bytes must equal `SHA1(passwordUTF16BE || "Mighty Aphrodite"
+/// || everything_before_those_20_bytes)` for V2, or the same without the
+/// `"Mighty Aphrodite"` salt for the older V1 format.
+fn verify_integrity(data: &[u8], version: Version, password: &str) -> Result<(), Error> {
+    if data.len() < 20 {
+        return Err(Error::truncated(20));
+    }
+    let (body, trailer) = data.split_at(data.len() - 20);
+
+    let mut input = crypto::password_to_utf16be(password);
+    if version == Version::V2 {
+        input.extend_from_slice(INTEGRITY_SALT);
     }
+    input.extend_from_slice(body);
+
+    if crypto::sha1(&input) != trailer {
+        return Err(Error::IntegrityCheckFailed);
+    }
+    Ok(())
+}
+
+/// A single decoded entry, as yielded by [`Store::entries`].
+#[derive(Debug)]
+pub enum Entry {
+    Cert(Box<CertInfo>),
+    KeyPair(KeyPair),
+    SecretKey(SecretKeyEntry),
 }
 
-fn process_cert<T>(data: &mut BufReader<T>) -> Result<CertInfo, Box<dyn Error>>
-where
-    T: Read,
-{
-    let alias = read::read_str(data)?;
-    let timestamp = read::read_timestamp(data)?;
-    let certificate = read::read_cert(data)?;
+struct EntryIter<R> {
+    reader: R,
+    remaining: u32,
+}
+
+impl<R: JksReader> Iterator for EntryIter<R> {
+    type Item = Result<Entry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let entry = self.read_one();
+        if entry.is_err() {
+            // Stop instead of reading past a framing error on a stream
+            // whose position we can no longer trust.
+            self.remaining = 0;
+        }
+        Some(entry)
+    }
+}
+
+impl<R: JksReader> EntryIter<R> {
+    fn read_one(&mut self) -> Result<Entry, Error> {
+        let entry_type = EntryType::try_from(self.reader.read_u32()?)?;
+        match entry_type {
+            EntryType::KeyPair => process_key_pair(&mut self.reader).map(Entry::KeyPair),
+            EntryType::Certs => process_cert(&mut self.reader).map(Box::new).map(Entry::Cert),
+            EntryType::SecretKey => process_secret_key(&mut self.reader).map(Entry::SecretKey),
+        }
+    }
+}
+
+fn process_cert<R: JksReader>(data: &mut R) -> Result<CertInfo, Error> {
+    let alias = data.read_str()?;
+    let timestamp = data.read_timestamp()?;
+    let certificate = data.read_cert()?;
     Ok(CertInfo {
         alias,
         timestamp,
@@ -139,27 +370,19 @@ where
     })
 }
 
-fn process_key_pair<T>(
-    data: &mut BufReader<T>,
-    opts: &mut Options,
-) -> Result<KeyPair, Box<dyn Error>>
-where
-    T: Read,
-{
-    let alias = read::read_str(data)?;
-    let timestamp = read::read_timestamp(data)?;
-    let _password = opts.key_passwords.get(&alias).unwrap_or(&opts.password);
-    let enc_key_len = u32::from_be_bytes(read::read_u32(data)?);
-    let enc_key = read::read_bytes(data, enc_key_len as usize)?;
-    let certs_entries_count = u32::from_be_bytes(read::read_u32(data)?);
+fn process_key_pair<R: JksReader>(data: &mut R) -> Result<KeyPair, Error> {
+    let alias = data.read_str()?;
+    let timestamp = data.read_timestamp()?;
+    let enc_key_len = u32::from_be_bytes(data.read_u32()?);
+    let enc_key = data.read_bytes(enc_key_len as usize)?;
+    let certs_entries_count = u32::from_be_bytes(data.read_u32()?);
     let mut cert_chains = vec![];
     for _ in 0..certs_entries_count {
-        let cert = read_cert(data)?;
-        let kps = KeyPairCert {
+        let cert = data.read_cert()?;
+        cert_chains.push(KeyPairCert {
             raw: cert.raw,
             cert: cert.cert,
-        };
-        cert_chains.push(kps);
+        });
     }
 
     Ok(KeyPair {
@@ -169,3 +392,82 @@ where
         cert_chain: cert_chains,
     })
 }
+
+fn process_secret_key<R: JksReader>(data: &mut R) -> Result<SecretKeyEntry, Error> {
+    let alias = data.read_str()?;
+    let timestamp = data.read_timestamp()?;
+    let (_fields, sealed_object) = jceks::read_sealed_object_recording(data)?;
+    Ok(SecretKeyEntry {
+        alias,
+        timestamp,
+        sealed_object,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_parse_round_trip() {
+        let password = "changeit";
+        let store = StoreBuilder::new()
+            .add_key_pair(
+                "alias1",
+                1_700_000_000,
+                b"fake PrivateKeyInfo DER",
+                password,
+                vec![],
+            )
+            .unwrap()
+            .build();
+
+        let opts = Options {
+            password: password.to_owned(),
+            ..Options::default()
+        };
+        let mut bytes = vec![];
+        store.write(&mut bytes, &opts).unwrap();
+
+        let parsed = Store::parse(&bytes, Some(opts)).unwrap();
+
+        assert_eq!(parsed.key_pairs.len(), 1);
+        assert_eq!(parsed.key_pairs[0].alias, "alias1");
+        assert_eq!(parsed.key_pairs[0].timestamp, 1_700_000_000);
+        assert_eq!(
+            parsed.key_pairs[0].decrypt(password).unwrap(),
+            b"fake PrivateKeyInfo DER"
+        );
+    }
+
+    #[test]
+    fn secret_key_entry_decrypt_round_trip() {
+        let password = "changeit";
+        let salt = [9u8; 8];
+        let iterations = 1000u32;
+        let plaintext = b"fake serialized SecretKeySpec".to_vec();
+        let encrypted_content =
+            crypto::encrypt_jceks_secret_key(&plaintext, &salt, iterations, password).unwrap();
+
+        // DER-encode PBEParameterSpec { salt OCTET STRING, iterationCount INTEGER }.
+        let mut params_content = vec![0x04, salt.len() as u8];
+        params_content.extend_from_slice(&salt);
+        params_content.extend_from_slice(&[0x02, 0x02, 0x03, 0xE8]); // INTEGER 1000
+        let mut encoded_params = vec![0x30, params_content.len() as u8];
+        encoded_params.extend_from_slice(&params_content);
+
+        let fields = jceks::SealedObjectFields {
+            encoded_params,
+            encrypted_content,
+            params_alg: "PBEWithMD5AndTripleDES".to_owned(),
+            seal_alg: "PBEWithMD5AndTripleDES".to_owned(),
+        };
+        let entry = SecretKeyEntry {
+            alias: "secret1".to_owned(),
+            timestamp: 1_700_000_000,
+            sealed_object: jceks::write_sealed_object(&fields),
+        };
+
+        assert_eq!(entry.decrypt(password).unwrap(), plaintext);
+    }
+}
@@ -0,0 +1,413 @@
+//! A lazy parsing mode that walks the entry framing of a keystore without
+//! decoding any certificate, following the `RawCertParser`/`RawCert` split
+//! Sequoia uses for OpenPGP certificates. Use this when a caller only needs
+//! to find one alias or list aliases in a large store, and wants to avoid
+//! paying for an `X509Certificate::from_der` on every entry.
+
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use x509_certificate::certificate::X509Certificate;
+
+use crate::{jceks, Cert, CertInfo, Error, KeyPair, KeyPairCert, SecretKeyEntry, MAGIC};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawEntryType {
+    KeyPair,
+    Certs,
+    /// A JCEKS `SecretKeyEntry`, absent from plain JKS stores.
+    SecretKey,
+}
+
+/// A promoted [`RawEntry`], produced by [`RawEntry::parse`].
+#[derive(Debug)]
+pub enum ParsedRawEntry {
+    Cert(Box<CertInfo>),
+    KeyPair(KeyPair),
+    SecretKey(SecretKeyEntry),
+}
+
+/// A certificate, key-pair or secret-key entry whose certificates have not
+/// been decoded yet; only byte offsets into the original keystore buffer
+/// are recorded.
+#[derive(Debug)]
+pub struct RawEntry<'a> {
+    entry_type: RawEntryType,
+    alias: String,
+    timestamp: u64,
+    cert_der: Option<&'a [u8]>,
+    encrypted_key: Option<&'a [u8]>,
+    cert_chain_ders: Vec<&'a [u8]>,
+    sealed_object: Option<&'a [u8]>,
+}
+
+impl<'a> RawEntry<'a> {
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn entry_type(&self) -> RawEntryType {
+        self.entry_type
+    }
+
+    /// The DER bytes of this entry's leading certificate, if any: the
+    /// trusted certificate itself for a [`RawEntryType::Certs`] entry, or
+    /// the end-entity certificate for a [`RawEntryType::KeyPair`] entry.
+    pub fn raw_cert_der(&self) -> Option<&'a [u8]> {
+        self.cert_der.or_else(|| self.cert_chain_ders.first().copied())
+    }
+
+    /// Promotes this entry, parsing every certificate it carries.
+    pub fn parse(&self) -> Result<ParsedRawEntry, Error> {
+        match self.entry_type {
+            RawEntryType::Certs => {
+                let der = self
+                    .cert_der
+                    .expect("a Certs entry always carries a certificate");
+                let cert = X509Certificate::from_der(der)?;
+                Ok(ParsedRawEntry::Cert(Box::new(CertInfo {
+                    alias: self.alias.clone(),
+                    timestamp: self.timestamp,
+                    certificate: Cert {
+                        raw: der.to_vec(),
+                        cert,
+                    },
+                })))
+            }
+            RawEntryType::KeyPair => {
+                let mut cert_chain = vec![];
+                for der in &self.cert_chain_ders {
+                    let cert = X509Certificate::from_der(*der)?;
+                    cert_chain.push(KeyPairCert {
+                        raw: der.to_vec(),
+                        cert,
+                    });
+                }
+                Ok(ParsedRawEntry::KeyPair(KeyPair {
+                    alias: self.alias.clone(),
+                    timestamp: self.timestamp,
+                    encrypted_key: self.encrypted_key.unwrap_or_default().to_vec(),
+                    cert_chain,
+                }))
+            }
+            RawEntryType::SecretKey => {
+                let sealed_object = self
+                    .sealed_object
+                    .expect("a SecretKey entry always carries a sealed object");
+                Ok(ParsedRawEntry::SecretKey(SecretKeyEntry {
+                    alias: self.alias.clone(),
+                    timestamp: self.timestamp,
+                    sealed_object: sealed_object.to_vec(),
+                }))
+            }
+        }
+    }
+}
+
+/// A keystore parsed only as far as entry framing: aliases, timestamps and
+/// byte offsets, with no ASN.1 decoding performed.
+#[derive(Debug)]
+pub struct RawStore<'a> {
+    pub entries: Vec<RawEntry<'a>>,
+}
+
+impl<'a> RawStore<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let mut cursor = Cursor::new(data);
+
+        let magic = read_u32(&mut cursor)?.to_be_bytes();
+        if magic != MAGIC {
+            return Err(Error::BadMagic { got: magic });
+        }
+        // V1 and V2 share entry framing (see crate::Version); only the
+        // trailing integrity digest differs, which this lazy mode never
+        // reads, so both are accepted here exactly as Store::parse does.
+        let version = u32::from_be_bytes(read_u32(&mut cursor)?.to_be_bytes());
+        if version != 1 && version != 2 {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let count = read_u32(&mut cursor)?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            entries.push(read_raw_entry(data, &mut cursor)?);
+        }
+
+        Ok(RawStore { entries })
+    }
+}
+
+fn read_raw_entry<'a>(data: &'a [u8], cursor: &mut Cursor<&[u8]>) -> Result<RawEntry<'a>, Error> {
+    let entry_type_tag = read_u32(cursor)?;
+    let entry_type = match entry_type_tag {
+        1 => RawEntryType::KeyPair,
+        2 => RawEntryType::Certs,
+        3 => RawEntryType::SecretKey,
+        other => return Err(Error::InvalidEntryType(other)),
+    };
+    let alias = read_str(cursor)?;
+    let timestamp = read_u64(cursor)?;
+
+    match entry_type {
+        RawEntryType::Certs => {
+            let cert_der = read_cert_der(data, cursor)?;
+            Ok(RawEntry {
+                entry_type,
+                alias,
+                timestamp,
+                cert_der: Some(cert_der),
+                encrypted_key: None,
+                cert_chain_ders: vec![],
+                sealed_object: None,
+            })
+        }
+        RawEntryType::KeyPair => {
+            let key_len = read_u32(cursor)? as usize;
+            let encrypted_key = read_slice(data, cursor, key_len)?;
+
+            let chain_len = read_u32(cursor)?;
+            let mut cert_chain_ders = Vec::with_capacity(chain_len as usize);
+            for _ in 0..chain_len {
+                cert_chain_ders.push(read_cert_der(data, cursor)?);
+            }
+            Ok(RawEntry {
+                entry_type,
+                alias,
+                timestamp,
+                cert_der: None,
+                encrypted_key: Some(encrypted_key),
+                cert_chain_ders,
+                sealed_object: None,
+            })
+        }
+        RawEntryType::SecretKey => {
+            // The sealed object is a Java serialization stream with no
+            // length prefix of its own; jceks::read_sealed_object walks it
+            // tag by tag and stops exactly where it ends, so the consumed
+            // range can be recovered from the cursor position, the same
+            // zero-copy way read_cert_der/read_slice recover cert and key
+            // spans.
+            let start = cursor.position() as usize;
+            jceks::read_sealed_object(cursor)?;
+            let end = cursor.position() as usize;
+            Ok(RawEntry {
+                entry_type,
+                alias,
+                timestamp,
+                cert_der: None,
+                encrypted_key: None,
+                cert_chain_ders: vec![],
+                sealed_object: Some(&data[start..end]),
+            })
+        }
+    }
+}
+
+fn read_cert_der<'a>(data: &'a [u8], cursor: &mut Cursor<&[u8]>) -> Result<&'a [u8], Error> {
+    let cert_type = read_str(cursor)?;
+    if cert_type != "X.509" {
+        return Err(Error::BadCertType(cert_type));
+    }
+    let len = read_u32(cursor)? as usize;
+    read_slice(data, cursor, len)
+}
+
+/// Reads `len` bytes as a zero-copy slice of `data`, advancing `cursor`
+/// past them.
+#[track_caller]
+fn read_slice<'a>(
+    data: &'a [u8],
+    cursor: &mut Cursor<&[u8]>,
+    len: usize,
+) -> Result<&'a [u8], Error> {
+    let start = cursor.position() as usize;
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= data.len())
+        .ok_or_else(|| Error::truncated(len))?;
+    cursor
+        .seek(SeekFrom::Start(end as u64))
+        .map_err(|e| Error::Io(e.to_string()))?;
+    Ok(&data[start..end])
+}
+
+#[track_caller]
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> Result<u32, Error> {
+    let mut buf = [0u8; 4];
+    read_exact(cursor, &mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+#[track_caller]
+fn read_u64(cursor: &mut Cursor<&[u8]>) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    read_exact(cursor, &mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+#[track_caller]
+fn read_str(cursor: &mut Cursor<&[u8]>) -> Result<String, Error> {
+    let mut len_buf = [0u8; 2];
+    read_exact(cursor, &mut len_buf)?;
+    let len = u16::from_be_bytes(len_buf) as usize;
+    let mut buf = vec![0u8; len];
+    read_exact(cursor, &mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+#[track_caller]
+fn read_exact(cursor: &mut Cursor<&[u8]>, buf: &mut [u8]) -> Result<(), Error> {
+    use std::io::ErrorKind::UnexpectedEof;
+    match cursor.read_exact(buf) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == UnexpectedEof => Err(Error::truncated(buf.len())),
+        Err(e) => Err(Error::Io(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto, jceks, Options, StoreBuilder};
+    use x509_certificate::{EcdsaCurve, KeyAlgorithm, X509CertificateBuilder};
+
+    /// A real (if otherwise meaningless), freshly self-signed certificate,
+    /// since `X509Certificate::from_der` parses for real and a fixed fixture
+    /// expires; `StoreBuilder::add_cert`/`add_key_pair` reject anything else.
+    fn self_signed_cert_der() -> Vec<u8> {
+        let (cert, _key_pair) = X509CertificateBuilder::default()
+            .create_with_random_keypair(KeyAlgorithm::Ecdsa(EcdsaCurve::Secp256r1))
+            .unwrap();
+        cert.encode_der().unwrap()
+    }
+
+    #[test]
+    fn parse_round_trip_against_store_write() {
+        let password = "changeit";
+        let cert_der = self_signed_cert_der();
+        let chain_der = self_signed_cert_der();
+
+        let mut store = StoreBuilder::new()
+            .add_cert("trusted", 1_700_000_000, cert_der.clone())
+            .unwrap()
+            .add_key_pair(
+                "keypair1",
+                1_700_000_100,
+                b"fake PrivateKeyInfo DER",
+                password,
+                vec![chain_der.clone()],
+            )
+            .unwrap()
+            .build();
+
+        // StoreBuilder has no add_secret_key helper, but Store's fields are
+        // public, so a SecretKeyEntry is assembled the same way
+        // crate::tests::secret_key_entry_decrypt_round_trip does.
+        let salt = [9u8; 8];
+        let iterations = 1000u32;
+        let plaintext = b"fake serialized SecretKeySpec".to_vec();
+        let encrypted_content =
+            crypto::encrypt_jceks_secret_key(&plaintext, &salt, iterations, password).unwrap();
+        let mut params_content = vec![0x04, salt.len() as u8];
+        params_content.extend_from_slice(&salt);
+        params_content.extend_from_slice(&[0x02, 0x02, 0x03, 0xE8]); // INTEGER 1000
+        let mut encoded_params = vec![0x30, params_content.len() as u8];
+        encoded_params.extend_from_slice(&params_content);
+        let sealed_fields = jceks::SealedObjectFields {
+            encoded_params,
+            encrypted_content,
+            params_alg: "PBEWithMD5AndTripleDES".to_owned(),
+            seal_alg: "PBEWithMD5AndTripleDES".to_owned(),
+        };
+        store.secret_keys.push(SecretKeyEntry {
+            alias: "secret1".to_owned(),
+            timestamp: 1_700_000_200,
+            sealed_object: jceks::write_sealed_object(&sealed_fields),
+        });
+
+        let opts = Options {
+            password: password.to_owned(),
+            ..Options::default()
+        };
+        let mut bytes = vec![];
+        store.write(&mut bytes, &opts).unwrap();
+
+        // Store::write always emits key pairs, then certs, then secret
+        // keys, regardless of the order they were added in.
+        let raw = RawStore::parse(&bytes).unwrap();
+        assert_eq!(raw.entries.len(), 3);
+
+        let key_pair_entry = &raw.entries[0];
+        assert_eq!(key_pair_entry.alias(), "keypair1");
+        assert_eq!(key_pair_entry.timestamp(), 1_700_000_100);
+        assert_eq!(key_pair_entry.entry_type(), RawEntryType::KeyPair);
+        assert_eq!(key_pair_entry.raw_cert_der(), Some(chain_der.as_slice()));
+
+        let cert_entry = &raw.entries[1];
+        assert_eq!(cert_entry.alias(), "trusted");
+        assert_eq!(cert_entry.timestamp(), 1_700_000_000);
+        assert_eq!(cert_entry.entry_type(), RawEntryType::Certs);
+        assert_eq!(cert_entry.raw_cert_der(), Some(cert_der.as_slice()));
+
+        let secret_key_entry = &raw.entries[2];
+        assert_eq!(secret_key_entry.alias(), "secret1");
+        assert_eq!(secret_key_entry.timestamp(), 1_700_000_200);
+        assert_eq!(secret_key_entry.entry_type(), RawEntryType::SecretKey);
+        assert_eq!(secret_key_entry.raw_cert_der(), None);
+
+        match key_pair_entry.parse().unwrap() {
+            ParsedRawEntry::KeyPair(key_pair) => {
+                assert_eq!(key_pair.alias, "keypair1");
+                assert_eq!(key_pair.cert_chain.len(), 1);
+                assert_eq!(
+                    key_pair.decrypt(password).unwrap(),
+                    b"fake PrivateKeyInfo DER"
+                );
+            }
+            other => panic!("expected a KeyPair entry, got {other:?}"),
+        }
+
+        match cert_entry.parse().unwrap() {
+            ParsedRawEntry::Cert(info) => {
+                assert_eq!(info.alias, "trusted");
+                assert_eq!(info.certificate.raw, cert_der);
+            }
+            other => panic!("expected a Cert entry, got {other:?}"),
+        }
+
+        match secret_key_entry.parse().unwrap() {
+            ParsedRawEntry::SecretKey(secret_key) => {
+                assert_eq!(secret_key.alias, "secret1");
+                assert_eq!(secret_key.decrypt(password).unwrap(), plaintext);
+            }
+            other => panic!("expected a SecretKey entry, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn truncated_store_is_rejected_not_panicked() {
+        let password = "changeit";
+        let store = StoreBuilder::new()
+            .add_cert("trusted", 1_700_000_000, self_signed_cert_der())
+            .unwrap()
+            .build();
+        let opts = Options {
+            password: password.to_owned(),
+            ..Options::default()
+        };
+        let mut bytes = vec![];
+        store.write(&mut bytes, &opts).unwrap();
+
+        // Cut the buffer off mid-certificate (well before the trailing
+        // 20-byte integrity MAC RawStore::parse never looks at), inside
+        // read_slice's bounds check.
+        let truncated = &bytes[..bytes.len() - 30];
+        assert!(matches!(
+            RawStore::parse(truncated),
+            Err(Error::Truncated { .. })
+        ));
+    }
+}
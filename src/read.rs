@@ -1,113 +1,92 @@
 use super::Cert;
-use std::error::Error;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use crate::Error;
+use std::io::Read;
 use x509_certificate::certificate::X509Certificate;
-pub(crate) fn read_cert<T>(data: &mut BufReader<T>) -> Result<Cert, Box<dyn Error>>
-where
-    T: Read,
-{
-    let cert_type = read_str(data)?;
-    if !cert_type.eq("X.509") {
-        return Err(format!("not supported certificate type: {}", cert_type))?;
+
+/// The low-level decoding primitives the JKS/JCEKS framing is built from.
+///
+/// Free functions bound to `BufReader<T>` would only ever work over one
+/// backend; this trait lets `Store::entries` run over any `Read`
+/// implementation, with a blanket impl covering the common case.
+/// `read_bytes` returns an owned `Vec<u8>`, so this isn't a fit for
+/// [`crate::raw`]'s zero-copy entry framing, which borrows slices directly
+/// out of the original buffer instead; that module keeps its own
+/// `Cursor`-bound reading functions.
+pub trait JksReader {
+    fn read_u16(&mut self) -> Result<[u8; 2], Error>;
+    fn read_u32(&mut self) -> Result<[u8; 4], Error>;
+    fn read_u64(&mut self) -> Result<[u8; 8], Error>;
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Error>;
+
+    fn read_str(&mut self) -> Result<String, Error> {
+        let length = u16::from_be_bytes(self.read_u16()?);
+        let buf = self.read_bytes(length as usize)?;
+        Ok(String::from_utf8(buf)?)
     }
-    let cert_length = read_u32(data)?;
-    let cert_der = read_bytes(data, u32::from_be_bytes(cert_length) as usize)?;
-    let parsed_cert = X509Certificate::from_der(cert_der.clone())?;
-    Ok(Cert {
-        raw: cert_der,
-        cert: parsed_cert,
-    })
-}
 
-pub(crate) fn read_bytes<T>(data: &mut BufReader<T>, len: usize) -> Result<Vec<u8>, String>
-where
-    T: Read,
-{
-    use std::io::ErrorKind::UnexpectedEof;
-    let mut buf: Vec<u8> = vec![0; len];
-    match data.read_exact(&mut buf) {
-        Ok(_) => Ok(buf),
-        Err(e) if e.kind() == UnexpectedEof => Err(format!(
-            "buffer is too short, at least {} are required",
-            len
-        )),
-        Err(e) => Err(format!("error reading bytes: {e}")),
+    fn read_timestamp(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.read_u64()?))
     }
-}
 
-pub(crate) fn read_u16<T>(data: &mut BufReader<T>) -> Result<[u8; 2], String>
-where
-    T: Read,
-{
-    use std::io::ErrorKind::UnexpectedEof;
-    let mut buf = [0u8; 2];
-    match data.read_exact(&mut buf) {
-        Ok(_) => Ok(buf),
-        Err(e) if e.kind() == UnexpectedEof => {
-            Err("buffer is too short, at least 2 bytes are required".to_owned())
+    fn read_cert(&mut self) -> Result<Cert, Error> {
+        let cert_type = self.read_str()?;
+        if !cert_type.eq("X.509") {
+            return Err(Error::BadCertType(cert_type));
         }
-        Err(e) => Err(format!("error reading bytes: {}", e)),
+        let cert_length = u32::from_be_bytes(self.read_u32()?);
+        let cert_der = self.read_bytes(cert_length as usize)?;
+        let parsed_cert = X509Certificate::from_der(cert_der.clone())?;
+        Ok(Cert {
+            raw: cert_der,
+            cert: parsed_cert,
+        })
     }
 }
 
-pub(crate) fn read_u32<T>(data: &mut BufReader<T>) -> Result<[u8; 4], String>
-where
-    T: Read,
-{
-    use std::io::ErrorKind::UnexpectedEof;
-    let mut buf = [0u8; 4];
-    match data.read_exact(&mut buf) {
-        Ok(_) => Ok(buf),
-        Err(e) if e.kind() == UnexpectedEof => {
-            Err("buffer is too short, at least 4 bytes are required".to_string())
-        }
-        Err(e) => Err(format!("error reading bytes: {}", e)),
+impl<T: Read> JksReader for T {
+    #[track_caller]
+    fn read_u16(&mut self) -> Result<[u8; 2], Error> {
+        let mut buf = [0u8; 2];
+        read_exact(self, &mut buf)?;
+        Ok(buf)
     }
-}
 
-pub(crate) fn read_u64<T>(data: &mut BufReader<T>) -> Result<[u8; 8], String>
-where
-    T: Read,
-{
-    use std::io::ErrorKind::UnexpectedEof;
-    let mut buf = [0u8; 8];
-    match data.read_exact(&mut buf) {
-        Ok(_) => Ok(buf),
-        Err(e) if e.kind() == UnexpectedEof => {
-            Err("buffer is too short, at least 8 bytes are required".to_string())
-        }
-        Err(e) => Err(format!("error reading bytes: {}", e)),
+    #[track_caller]
+    fn read_u32(&mut self) -> Result<[u8; 4], Error> {
+        let mut buf = [0u8; 4];
+        read_exact(self, &mut buf)?;
+        Ok(buf)
     }
-}
 
-pub(crate) fn read_str<T>(data: &mut BufReader<T>) -> Result<String, String>
-where
-    T: Read,
-{
-    let length = u16::from_be_bytes(read_u16(data)?);
-    let mut buf: Vec<u8> = vec![0; length as usize];
-    match data.read_exact(&mut buf) {
-        Ok(()) => {}
-        Err(e) => return Err(format!("failed to read string: {}", e)),
+    #[track_caller]
+    fn read_u64(&mut self) -> Result<[u8; 8], Error> {
+        let mut buf = [0u8; 8];
+        read_exact(self, &mut buf)?;
+        Ok(buf)
     }
-    match String::from_utf8(buf) {
-        Ok(res) => Ok(res),
-        Err(e) => Err(format!("{}", e)),
+
+    #[track_caller]
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf: Vec<u8> = vec![0; len];
+        read_exact(self, &mut buf)?;
+        Ok(buf)
     }
 }
 
-pub(crate) fn read_timestamp<T>(data: &mut BufReader<T>) -> Result<u64, String>
-where
-    T: Read,
-{
-    let timestamp = read_u64(data)?;
-    Ok(u64::from_be_bytes(timestamp))
+#[track_caller]
+fn read_exact<T: Read + ?Sized>(data: &mut T, buf: &mut [u8]) -> Result<(), Error> {
+    use std::io::ErrorKind::UnexpectedEof;
+    match data.read_exact(buf) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == UnexpectedEof => Err(Error::truncated(buf.len())),
+        Err(e) => Err(Error::Io(e.to_string())),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::MAGIC;
-    use std::io;
+    use std::io::{self, BufReader};
 
     use super::*;
 
@@ -116,7 +95,7 @@ mod tests {
         let data = [0xfeu8, 0xed, 0xfe, 0xed, 0x00];
         let cursor = io::Cursor::new(data);
         let mut buf = BufReader::new(cursor);
-        let four_bytes = read_u32(&mut buf).unwrap();
+        let four_bytes = buf.read_u32().unwrap();
         assert_eq!(four_bytes.len(), 4);
         assert_eq!(four_bytes, MAGIC);
         // check buffer contain last 1 bytes
@@ -129,7 +108,7 @@ mod tests {
         let data = [0x00u8, 0x00, 0x00, 0x02];
         let cursor = io::Cursor::new(data);
         let mut buf = BufReader::new(cursor);
-        let four_bytes = read_u32(&mut buf).unwrap();
+        let four_bytes = buf.read_u32().unwrap();
         let d = u32::from_be_bytes(four_bytes);
         assert_eq!(d, 2);
     }
@@ -0,0 +1,53 @@
+//! Byte-level encoders mirroring the `read` module's framing, used by
+//! [`crate::Store::write`].
+
+use crate::{CertInfo, KeyPair, SecretKeyEntry};
+
+pub(crate) fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn write_str(buf: &mut Vec<u8>, value: &str) {
+    write_u16(buf, value.len() as u16);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+pub(crate) fn write_cert(buf: &mut Vec<u8>, raw: &[u8]) {
+    write_str(buf, "X.509");
+    write_u32(buf, raw.len() as u32);
+    buf.extend_from_slice(raw);
+}
+
+pub(crate) fn write_cert_entry(buf: &mut Vec<u8>, cert: &CertInfo) {
+    write_u32(buf, 2);
+    write_str(buf, &cert.alias);
+    write_u64(buf, cert.timestamp);
+    write_cert(buf, &cert.certificate.raw);
+}
+
+pub(crate) fn write_secret_key_entry(buf: &mut Vec<u8>, secret_key: &SecretKeyEntry) {
+    write_u32(buf, 3);
+    write_str(buf, &secret_key.alias);
+    write_u64(buf, secret_key.timestamp);
+    buf.extend_from_slice(&secret_key.sealed_object);
+}
+
+pub(crate) fn write_key_pair_entry(buf: &mut Vec<u8>, key_pair: &KeyPair) {
+    write_u32(buf, 1);
+    write_str(buf, &key_pair.alias);
+    write_u64(buf, key_pair.timestamp);
+    write_u32(buf, key_pair.encrypted_key.len() as u32);
+    buf.extend_from_slice(&key_pair.encrypted_key);
+    write_u32(buf, key_pair.cert_chain.len() as u32);
+    for cert in &key_pair.cert_chain {
+        write_cert(buf, &cert.raw);
+    }
+}